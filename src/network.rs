@@ -1,22 +1,63 @@
 use HashMap;
+use HashSet;
 use log;
 use message::{Action, Message};
 use node;
 use params::Params;
 use prefix::Prefix;
+use prefix_trie::PrefixTrie;
 use section::Section;
+use section_policy::{DefaultSectionPolicy, SectionPolicy};
 use stats::{Aggregator, Distribution, Stats};
 use std::ops::AddAssign;
+use transport::{Transport, TransportConfig};
 
 pub struct Network {
     params: Params,
     stats: Stats,
     sections: HashMap<Prefix, Section>,
+    transport: Transport,
+    policy: Box<SectionPolicy>,
+    /// Index mirroring the live prefixes in `sections`, used to route
+    /// `Send`/`Merge` in O(prefix length) instead of scanning `sections`.
+    routing: PrefixTrie,
+    /// Running total of duplicate `Merge`/`Split` actions suppressed by
+    /// `dedup_actions`, across the whole run.
+    total_suppressed: u64,
+    /// One `TickReport` per completed `tick`, indexed by iteration. `Stats`
+    /// (see `stats.rs`) already gives this kind of per-iteration history
+    /// for merges/splits/relocations/rejections, but `stats.rs` isn't part
+    /// of this source tree to add drops/retransmits/latency/suppressed
+    /// fields to, so it's kept here instead, alongside it.
+    tick_history: Vec<TickReport>,
 }
 
 impl Network {
-    /// Create new simulated network with the given parameters.
+    /// Create new simulated network with the given parameters, using the
+    /// default split/merge policy (the simulation's original, hardcoded
+    /// behavior) and an instantaneous, perfectly reliable transport (also
+    /// the original behavior). Join-candidate rejection isn't part of
+    /// `SectionPolicy` and so isn't pluggable here either — see
+    /// `SectionPolicy`'s doc comment for why.
     pub fn new(params: Params) -> Self {
+        Self::with_policy(params, Box::new(DefaultSectionPolicy))
+    }
+
+    /// Create new simulated network with the given parameters and a custom
+    /// `SectionPolicy`, allowing split/merge growth strategies other than
+    /// the default to be swapped in without touching `section`/`node`.
+    pub fn with_policy(params: Params, policy: Box<SectionPolicy>) -> Self {
+        Self::with_policy_and_transport(params, policy, TransportConfig::default())
+    }
+
+    /// Create new simulated network with the given parameters, policy, and
+    /// transport configuration, allowing message loss/delay/retransmission
+    /// to be studied independently of the growth strategy in use.
+    pub fn with_policy_and_transport(
+        params: Params,
+        policy: Box<SectionPolicy>,
+        transport_config: TransportConfig,
+    ) -> Self {
         let mut sections = HashMap::default();
         let _ = sections.insert(Prefix::EMPTY, Section::new(Prefix::EMPTY));
 
@@ -24,6 +65,11 @@ impl Network {
             params,
             stats: Stats::new(),
             sections,
+            transport: Transport::new(transport_config),
+            policy,
+            routing: PrefixTrie::new(),
+            total_suppressed: 0,
+            tick_history: Vec::new(),
         }
     }
 
@@ -32,6 +78,17 @@ impl Network {
         let mut actions = Vec::new();
         let mut stats = TickStats::new();
 
+        // Snapshot the transport's cumulative counters so this tick's own
+        // deltas can be recorded in `tick_history` below, alongside
+        // `stats.suppressed`.
+        let drops_before = self.transport.drops();
+        let retransmits_before = self.transport.retransmits();
+        let delivered_before = self.transport.delivered();
+        let total_latency_before = self.transport.total_latency();
+
+        stats += self.deliver_due(iteration);
+        self.transport.retransmit_due(iteration);
+
         for section in self.sections.values_mut() {
             section.prepare();
         }
@@ -45,9 +102,18 @@ impl Network {
                 break;
             }
 
-            stats += self.handle_actions(&mut actions)
+            stats += self.handle_actions(iteration, &mut actions);
+
+            // Deliver anything `handle_actions` just sent with zero latency
+            // before the next pass of this loop, so it's visible to its
+            // target section's `tick()` within the same simulation tick it
+            // was sent — matching the original inline `section.receive` this
+            // `Transport`/`Action::Send` indirection replaced.
+            stats += self.deliver_due(iteration);
         }
 
+        self.total_suppressed += stats.suppressed;
+
         self.stats.record(
             iteration,
             self.sections
@@ -61,9 +127,88 @@ impl Network {
             stats.rejections,
         );
 
+        let delivered_this_tick = self.transport.delivered() - delivered_before;
+        let latency_this_tick = self.transport.total_latency() - total_latency_before;
+
+        self.tick_history.push(TickReport {
+            iteration,
+            drops: self.transport.drops() - drops_before,
+            retransmits: self.transport.retransmits() - retransmits_before,
+            average_latency: if delivered_this_tick == 0 {
+                0.0
+            } else {
+                latency_this_tick as f64 / delivered_this_tick as f64
+            },
+            suppressed: stats.suppressed,
+        });
+
         self.validate();
     }
 
+    /// Number of duplicate `Merge`/`Split` actions suppressed by
+    /// `dedup_actions` so far, across the whole run. See `tick_history` for
+    /// the same count broken out per iteration.
+    pub fn suppressed_actions(&self) -> u64 {
+        self.total_suppressed
+    }
+
+    /// Messages the transport has dropped (outright, or after exhausting
+    /// their retransmit budget) so far, across the whole run. See
+    /// `tick_history` for the same count broken out per iteration.
+    pub fn transport_drops(&self) -> u64 {
+        self.transport.drops()
+    }
+
+    /// Guaranteed-delivery messages the transport has retransmitted so far,
+    /// across the whole run. See `tick_history` for the same count broken
+    /// out per iteration.
+    pub fn transport_retransmits(&self) -> u64 {
+        self.transport.retransmits()
+    }
+
+    /// Mean end-to-end delivery latency in ticks, across every message the
+    /// transport has delivered so far. See `tick_history` for the same
+    /// figure broken out per iteration.
+    pub fn average_message_latency(&self) -> f64 {
+        self.transport.average_latency()
+    }
+
+    /// Per-iteration transport and dedup metrics, one `TickReport` per
+    /// completed `tick`, in iteration order. Gives drops/retransmits/
+    /// average latency/suppressed-count the same per-iteration resolution
+    /// `stats()` already gives merges/splits/relocations/rejections —
+    /// without needing `stats.rs` (not part of this source tree) to grow
+    /// fields of its own for them.
+    pub fn tick_history(&self) -> &[TickReport] {
+        &self.tick_history
+    }
+
+    /// Drain messages the transport has scheduled for delivery by
+    /// `iteration` and hand each one to its matching section.
+    fn deliver_due(&mut self, iteration: u64) -> TickStats {
+        let mut stats = TickStats::new();
+
+        for message in self.transport.drain_due(iteration) {
+            self.deliver(message, &mut stats);
+        }
+
+        stats
+    }
+
+    fn deliver(&mut self, message: Message, stats: &mut TickStats) {
+        let target = message.target();
+        let prefix = self.routing.longest_matching(target).unwrap_or_else(|| {
+            panic!("No section maching {:?} found", target)
+        });
+        let section = self.sections.get_mut(&prefix).unwrap();
+
+        if let Message::RelocateCommit { .. } = message {
+            stats.relocations += 1;
+        }
+
+        section.receive(message)
+    }
+
     pub fn stats(&self) -> &Stats {
         &self.stats
     }
@@ -105,20 +250,37 @@ impl Network {
     }
 
 
-    fn handle_actions(&mut self, actions: &mut Vec<Action>) -> TickStats {
+    fn handle_actions(&mut self, iteration: u64, actions: &mut Vec<Action>) -> TickStats {
         let mut stats = TickStats::new();
 
+        Self::dedup_actions(actions, &mut stats);
+
         for action in actions.drain(..) {
             match action {
                 Action::Reject(_) => {
                     stats.rejections += 1;
                 }
                 Action::Merge(target) => {
-                    let sources: Vec<_> = self.sections
-                        .keys()
-                        .filter(|prefix| prefix.is_descendant(&target))
-                        .cloned()
-                        .collect();
+                    let sources = self.routing.descendants(target);
+
+                    // `descendants` includes `target` itself when it's
+                    // currently a live leaf (see its doc comment), so a
+                    // duplicate `Merge(target)` for a target already
+                    // resolved — whether earlier this same tick or in a
+                    // prior one — comes back as `[target]`, not `[]`. The
+                    // routing trie is already the record of "already
+                    // committed" here, so this is where cross-tick
+                    // suppression belongs: count it the same as a
+                    // same-batch duplicate instead of letting it pass
+                    // through as a silent no-op.
+                    if sources.len() == 1 && sources[0] == target {
+                        stats.suppressed += 1;
+                        debug!(
+                            "Merge({}) is a stray duplicate of an already-committed merge",
+                            log::prefix(&target)
+                        );
+                        continue;
+                    }
 
                     if sources.is_empty() {
                         // Merge action with the same target can be potentially
@@ -134,12 +296,31 @@ impl Network {
                         continue;
                     }
 
+                    // Ask the policy about every pre-merge section, not just
+                    // `sources[0]`: a stateful policy (e.g. hysteresis bands
+                    // tracked per section) needs to see and update both
+                    // sides of the merge, and the merge only proceeds if
+                    // neither side vetoes it. Collected eagerly rather than
+                    // via `Iterator::all` so a veto on an earlier source
+                    // doesn't short-circuit the later ones out of having
+                    // their state updated this tick.
+                    let verdicts: Vec<bool> = sources
+                        .iter()
+                        .map(|source| {
+                            self.policy.should_merge(&self.sections[source], &self.params, target)
+                        })
+                        .collect();
+                    if !verdicts.into_iter().all(|proceed| proceed) {
+                        continue;
+                    }
+
                     let sources: Vec<_> = sources
                         .into_iter()
                         .map(|source| self.sections.remove(&source).unwrap())
                         .collect();
 
                     stats.merges += 1;
+                    self.routing.merge(target);
 
                     let section = self.sections.entry(target).or_insert_with(
                         || Section::new(target),
@@ -148,35 +329,47 @@ impl Network {
                         section.merge(&self.params, source);
                     }
                 }
-                Action::Split(source) => {
-                    stats.splits += 1;
+                Action::Split(source_prefix) => {
+                    let proceed = match self.sections.get(&source_prefix) {
+                        Some(section) => self.policy.should_split(section, &self.params),
+                        None => {
+                            // This can happen for example in the following situation:
+                            // 1. Section P0 decides it needs to merge with P1.
+                            // 2. P1 gets new node (via join or relocation) which triggers
+                            //    a split.
+                            // 3. `Merge(P)` action is handled first, merging P0 and P1
+                            //    into P.
+                            // 4. `Split(P1)` action is handled next, but P1 is no longer there.
+                            //
+                            // This situation is valid, so it's OK to ignore the missing
+                            // sections here.
+                            //
+                            // On the other hand, this line should never be reached due to
+                            // `Split` being emitted more than once, because split can
+                            // only be triggered by join or relocation, and those happen
+                            // at most once per section tick.
+                            debug!(
+                                "Pre-split section {} not found",
+                                log::prefix(&source_prefix)
+                            );
+                            continue;
+                        }
+                    };
 
-                    let source = if let Some(section) = self.sections.remove(&source) {
-                        section
-                    } else {
-                        // This can happen for example in the following situation:
-                        // 1. Section P0 decides it needs to merge with P1.
-                        // 2. P1 gets new node (via join or relocation) which triggers
-                        //    a split.
-                        // 3. `Merge(P)` action is handled first, merging P0 and P1
-                        //    into P.
-                        // 4. `Split(P1)` action is handled next, but P1 is no longer there.
-                        //
-                        // This situation is valid, so it's OK to ignore the missing
-                        // sections here.
-                        //
-                        // On the other hand, this line should never be reached due to
-                        // `Split` being emitted more than once, because split can
-                        // only be triggered by join or relocation, and those happen
-                        // at most once per section tick.
-                        debug!("Pre-split section {} not found", log::prefix(&source));
+                    if !proceed {
                         continue;
-                    };
+                    }
+
+                    stats.splits += 1;
+
+                    let source = self.sections.remove(&source_prefix).unwrap();
 
                     let (target0, target1) = source.split(&self.params);
                     let prefix0 = target0.prefix();
                     let prefix1 = target1.prefix();
 
+                    self.routing.split(source_prefix, prefix0, prefix1);
+
                     assert!(
                         self.sections.insert(prefix0, target0).is_none(),
                         "section with prefix [{}] already exists",
@@ -189,19 +382,7 @@ impl Network {
                     );
                 }
                 Action::Send(message) => {
-                    let target = message.target();
-                    if let Some(section) = self.sections.values_mut().find(|section| {
-                        section.prefix().matches(target)
-                    })
-                    {
-                        if let Message::RelocateCommit { .. } = message {
-                            stats.relocations += 1;
-                        }
-
-                        section.receive(message)
-                    } else {
-                        panic!("No section maching {:?} found", target)
-                    }
+                    self.transport.send(iteration, message);
                 }
             }
         }
@@ -209,6 +390,62 @@ impl Network {
         stats
     }
 
+    /// Collapse `actions` so that each `Merge` target and each `Split`
+    /// source appears at most once *within this batch*. The long comments
+    /// in `handle_actions` document why the same `Merge` target can
+    /// legitimately be emitted more than once in a single pass (e.g. both
+    /// pre-merge sections losing a node in the same tick); a repeated
+    /// `Split` source is not expected, but is collapsed the same way. This
+    /// pass removes those repeats instead of doing redundant work.
+    ///
+    /// The backlog for this request asked for more than this: an
+    /// acknowledged-target set kept *on `Network`*, spanning ticks, so a
+    /// duplicate action arriving in a later tick (not just later in the same
+    /// batch) would also be suppressed. That was tried (the `known_targets`
+    /// field) and reverted, because a prefix's merge/split hasn't been
+    /// committed yet at dedup time (that happens below, in `handle_actions`,
+    /// once `should_merge`/`should_split` have had their say) — a set
+    /// spanning ticks would mark a prefix "known" before a policy even
+    /// decided whether to allow it, permanently suppressing a legitimately
+    /// vetoed (e.g. hysteresis-delayed) retry, and wrongly blocking a later,
+    /// unrelated action for the same prefix (e.g. a `Split` source being
+    /// merged back into once its two children re-converge).
+    ///
+    /// Cross-tick suppression is still delivered, just not from here: the
+    /// `Merge` arm in `handle_actions` already has to ask `self.routing`
+    /// whether `target`'s pre-merge sections still exist, and a target
+    /// `self.routing` reports as already-live (i.e. already committed, in
+    /// this tick or an earlier one) is exactly a cross-tick duplicate — so
+    /// that arm counts it in `stats.suppressed` directly instead of this
+    /// function needing its own committed-target set. That reuses state
+    /// that's already correct by construction (the trie is only ever
+    /// updated on an actual commit) instead of a second set that would need
+    /// its own insert/invalidate lifecycle to stay in sync with it.
+    ///
+    /// Takes no `&self`: this is a pure function of `actions`/`stats`, which
+    /// also means it's testable without a `Network` to construct (see the
+    /// tests below).
+    fn dedup_actions(actions: &mut Vec<Action>, stats: &mut TickStats) {
+        let mut seen_this_batch = HashSet::default();
+
+        actions.retain(|action| {
+            // Keyed by kind as well as prefix, so a `Split(P)` is never
+            // mistaken for a repeat of a `Merge(P)` in the same batch.
+            let key = match *action {
+                Action::Merge(target) => (true, target),
+                Action::Split(source) => (false, source),
+                _ => return true,
+            };
+
+            if seen_this_batch.insert(key) {
+                true
+            } else {
+                stats.suppressed += 1;
+                false
+            }
+        });
+    }
+
     fn validate(&self) {
         for section in self.sections.values() {
             if section.nodes().len() > self.params.max_section_size {
@@ -259,6 +496,7 @@ struct TickStats {
     splits: u64,
     relocations: u64,
     rejections: u64,
+    suppressed: u64,
 }
 
 impl TickStats {
@@ -268,6 +506,7 @@ impl TickStats {
             splits: 0,
             relocations: 0,
             rejections: 0,
+            suppressed: 0,
         }
     }
 }
@@ -278,5 +517,97 @@ impl AddAssign for TickStats {
         self.splits += other.splits;
         self.relocations += other.relocations;
         self.rejections += other.rejections;
+        self.suppressed += other.suppressed;
+    }
+}
+
+/// Transport health and dedup metrics for a single completed `tick`, as
+/// recorded in `Network::tick_history`.
+///
+/// `Stats` (see `stats.rs`) already gives merges/splits/relocations/
+/// rejections this same per-iteration resolution via `record`/`stats()`,
+/// but `stats.rs` isn't part of this source tree (unlike `transport.rs`,
+/// `section_policy.rs`, and `prefix_trie.rs`, which this series introduced
+/// from scratch), so there's no existing type there to add drop/retransmit/
+/// latency/suppressed fields to without guessing at its shape. This type
+/// gives the same resolution independently instead, entirely out of code
+/// this series already owns.
+pub struct TickReport {
+    pub iteration: u64,
+    /// Messages the transport dropped during this tick specifically (not
+    /// cumulative — see `Network::transport_drops` for the running total).
+    pub drops: u64,
+    /// Guaranteed-delivery messages the transport retransmitted during
+    /// this tick specifically.
+    pub retransmits: u64,
+    /// Mean end-to-end delivery latency, in ticks, across messages
+    /// delivered during this tick specifically; `0.0` if none were.
+    pub average_latency: f64,
+    /// Duplicate `Merge`/`Split` actions suppressed during this tick
+    /// specifically.
+    pub suppressed: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duplicate_merge_and_split_in_the_same_batch_are_suppressed() {
+        let children = Prefix::EMPTY.split();
+        let mut actions = vec![
+            Action::Merge(Prefix::EMPTY),
+            Action::Merge(Prefix::EMPTY),
+            Action::Split(children[0]),
+            Action::Split(children[0]),
+        ];
+        let mut stats = TickStats::new();
+
+        Network::dedup_actions(&mut actions, &mut stats);
+
+        assert!(actions == vec![Action::Merge(Prefix::EMPTY), Action::Split(children[0])]);
+    }
+
+    #[test]
+    fn suppressed_count_is_incremented_once_per_dropped_duplicate() {
+        let mut actions = vec![
+            Action::Merge(Prefix::EMPTY),
+            Action::Merge(Prefix::EMPTY),
+            Action::Merge(Prefix::EMPTY),
+        ];
+        let mut stats = TickStats::new();
+
+        Network::dedup_actions(&mut actions, &mut stats);
+
+        assert_eq!(stats.suppressed, 2);
+    }
+
+    #[test]
+    fn distinct_targets_are_not_suppressed() {
+        let children = Prefix::EMPTY.split();
+        let mut actions = vec![Action::Merge(children[0]), Action::Merge(children[1])];
+        let mut stats = TickStats::new();
+
+        Network::dedup_actions(&mut actions, &mut stats);
+
+        assert!(actions == vec![Action::Merge(children[0]), Action::Merge(children[1])]);
+        assert_eq!(stats.suppressed, 0);
+    }
+
+    #[test]
+    fn non_merge_split_actions_pass_through_untouched() {
+        let mut actions = vec![
+            Action::Reject(5),
+            Action::Reject(5),
+            Action::Merge(Prefix::EMPTY),
+        ];
+        let mut stats = TickStats::new();
+
+        Network::dedup_actions(&mut actions, &mut stats);
+
+        assert!(
+            actions == vec![Action::Reject(5), Action::Reject(5), Action::Merge(Prefix::EMPTY)]
+        );
+        assert_eq!(stats.suppressed, 0);
     }
 }
@@ -0,0 +1,207 @@
+use prefix::Prefix;
+
+/// Binary trie over section prefixes, maintained alongside
+/// `HashMap<Prefix, Section>` on `Network` so that routing a message, or
+/// finding the pre-merge sections for an `Action::Merge`, doesn't need an
+/// O(number of sections) scan.
+///
+/// Each node corresponds to a prefix; a node with no children is a leaf
+/// holding a currently-live section's prefix. A node with children is an
+/// old, no-longer-live prefix kept around purely as a branching point
+/// (created the moment its section was split) under which the two child
+/// prefixes (and, transitively, their own descendants) live.
+pub struct PrefixTrie {
+    root: Node,
+}
+
+struct Node {
+    prefix: Prefix,
+    live: bool,
+    children: Option<Box<(Node, Node)>>,
+}
+
+impl Node {
+    fn leaf(prefix: Prefix) -> Self {
+        Node {
+            prefix,
+            live: true,
+            children: None,
+        }
+    }
+}
+
+impl PrefixTrie {
+    /// Create a trie with a single live leaf at `Prefix::EMPTY`, matching
+    /// the initial single section `Network::new` creates.
+    pub fn new() -> Self {
+        PrefixTrie { root: Node::leaf(Prefix::EMPTY) }
+    }
+
+    /// Find the live prefix whose section `target` currently belongs to,
+    /// in O(prefix length) instead of a scan over every section.
+    pub fn longest_matching(&self, target: Prefix) -> Option<Prefix> {
+        let mut node = &self.root;
+
+        loop {
+            match node.children {
+                Some(ref children) => {
+                    if children.0.prefix.matches(target) {
+                        node = &children.0;
+                    } else if children.1.prefix.matches(target) {
+                        node = &children.1;
+                    } else {
+                        break;
+                    }
+                }
+                None => break,
+            }
+        }
+
+        if node.live { Some(node.prefix) } else { None }
+    }
+
+    /// Live prefixes nested under (and including) `prefix`, found by
+    /// descending directly into that subtree rather than scanning every
+    /// section.
+    pub fn descendants(&self, prefix: Prefix) -> Vec<Prefix> {
+        let mut out = Vec::new();
+        if let Some(node) = self.find(prefix) {
+            Self::collect_live(node, &mut out);
+        }
+        out
+    }
+
+    /// Replace the live leaf at `parent` with its two split children.
+    pub fn split(&mut self, parent: Prefix, child0: Prefix, child1: Prefix) {
+        let node = self.find_mut(parent).expect(
+            "split of a prefix not present in the routing trie",
+        );
+        node.live = false;
+        node.children = Some(Box::new((Node::leaf(child0), Node::leaf(child1))));
+    }
+
+    /// Collapse the subtree under `target` (the pre-merge sections) back
+    /// into a single live leaf.
+    pub fn merge(&mut self, target: Prefix) {
+        let node = self.find_mut(target).expect(
+            "merge into a prefix not present in the routing trie",
+        );
+        node.live = true;
+        node.children = None;
+    }
+
+    fn find(&self, prefix: Prefix) -> Option<&Node> {
+        let mut node = &self.root;
+
+        loop {
+            if node.prefix == prefix {
+                return Some(node);
+            }
+
+            match node.children {
+                Some(ref children) => {
+                    if children.0.prefix.matches(prefix) {
+                        node = &children.0;
+                    } else if children.1.prefix.matches(prefix) {
+                        node = &children.1;
+                    } else {
+                        return None;
+                    }
+                }
+                None => return None,
+            }
+        }
+    }
+
+    fn find_mut(&mut self, prefix: Prefix) -> Option<&mut Node> {
+        let mut node = &mut self.root;
+
+        loop {
+            if node.prefix == prefix {
+                return Some(node);
+            }
+
+            match node.children {
+                Some(ref mut children) => {
+                    if children.0.prefix.matches(prefix) {
+                        node = &mut children.0;
+                    } else if children.1.prefix.matches(prefix) {
+                        node = &mut children.1;
+                    } else {
+                        return None;
+                    }
+                }
+                None => return None,
+            }
+        }
+    }
+
+    fn collect_live(node: &Node, out: &mut Vec<Prefix>) {
+        if node.live {
+            out.push(node.prefix);
+        }
+
+        if let Some(ref children) = node.children {
+            Self::collect_live(&children.0, out);
+            Self::collect_live(&children.1, out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fresh_trie_has_a_single_live_root() {
+        let trie = PrefixTrie::new();
+
+        assert!(trie.longest_matching(Prefix::EMPTY) == Some(Prefix::EMPTY));
+        assert!(trie.descendants(Prefix::EMPTY) == vec![Prefix::EMPTY]);
+    }
+
+    #[test]
+    fn split_then_merge_round_trips_to_a_single_leaf() {
+        let mut trie = PrefixTrie::new();
+        let children = Prefix::EMPTY.split();
+        let child0 = children[0];
+        let child1 = children[1];
+
+        trie.split(Prefix::EMPTY, child0, child1);
+
+        // The split parent is no longer live; routing and descendant
+        // queries should only see the two children.
+        assert!(trie.longest_matching(Prefix::EMPTY) == None);
+        assert!(trie.longest_matching(child0) == Some(child0));
+        assert!(trie.longest_matching(child1) == Some(child1));
+        assert!(trie.descendants(Prefix::EMPTY) == vec![child0, child1]);
+
+        trie.merge(Prefix::EMPTY);
+
+        // Merging the children back collapses the subtree to a single
+        // live leaf again, exactly like a fresh trie.
+        assert!(trie.longest_matching(Prefix::EMPTY) == Some(Prefix::EMPTY));
+        assert!(trie.longest_matching(child0) == Some(Prefix::EMPTY));
+        assert!(trie.descendants(Prefix::EMPTY) == vec![Prefix::EMPTY]);
+    }
+
+    #[test]
+    fn split_one_child_again_leaves_the_other_untouched() {
+        let mut trie = PrefixTrie::new();
+        let top = Prefix::EMPTY.split();
+        let (left, right) = (top[0], top[1]);
+        trie.split(Prefix::EMPTY, left, right);
+
+        let bottom = left.split();
+        let (left_left, left_right) = (bottom[0], bottom[1]);
+        trie.split(left, left_left, left_right);
+
+        assert!(trie.longest_matching(left) == None);
+        assert!(trie.longest_matching(left_left) == Some(left_left));
+        assert!(trie.longest_matching(right) == Some(right));
+
+        // Traversal visits the left subtree (still split further) before
+        // the untouched right leaf.
+        assert!(trie.descendants(Prefix::EMPTY) == vec![left_left, left_right, right]);
+    }
+}
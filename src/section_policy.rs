@@ -0,0 +1,48 @@
+use params::Params;
+use prefix::Prefix;
+use section::Section;
+
+/// Swappable policy governing when a section's proposed split or merge is
+/// actually allowed to go ahead, analogous to a tantivy-style
+/// `MergePolicy`.
+///
+/// `Network` consults the policy once a section has already decided it
+/// wants to split or merge; the policy can veto or delay the action (e.g.
+/// to enforce hysteresis) without `section`/`node` needing to know
+/// anything about the strategy in use.
+///
+/// Note there's no `should_reject`: join-candidate rejection is decided
+/// inside `Section::tick` itself, before an `Action::Reject` is ever
+/// produced, so by the time `Network` sees one the decision has already
+/// been made — there's no hook here for a policy to veto it. A
+/// `should_reject` was tried (giving this trait the full "pluggable
+/// split/merge/reject policy" the request asked for) and removed once that
+/// constraint became clear; adding the hook for real means changing what
+/// `Section::tick` itself produces, which this series doesn't touch. So
+/// this is a narrower "pluggable split/merge policy," reject strategy still
+/// hardcoded in `section`/`node` — a real scope reduction from what was
+/// asked, not just a naming nit, and it needs sign-off from whoever filed
+/// this request rather than being treated as delivered in full.
+pub trait SectionPolicy {
+    /// Confirm (or veto) a split that `section` has proposed.
+    fn should_split(&mut self, section: &Section, params: &Params) -> bool;
+
+    /// Confirm (or veto) a merge of `section` into `target`. Returning
+    /// `false` delays the merge for this tick.
+    fn should_merge(&mut self, section: &Section, params: &Params, target: Prefix) -> bool;
+}
+
+/// The policy matching the simulation's original, hardcoded behavior:
+/// proposed splits and merges are always honored on the tick they're
+/// proposed.
+pub struct DefaultSectionPolicy;
+
+impl SectionPolicy for DefaultSectionPolicy {
+    fn should_split(&mut self, _section: &Section, _params: &Params) -> bool {
+        true
+    }
+
+    fn should_merge(&mut self, _section: &Section, _params: &Params, _target: Prefix) -> bool {
+        true
+    }
+}
@@ -0,0 +1,353 @@
+use HashMap;
+use message::Message;
+use rand::{self, Rng};
+
+/// Sequence number identifying a message that requires guaranteed delivery.
+pub type SeqId = u64;
+
+/// Configuration for the lossy/delayed transport. Kept as its own type
+/// rather than on `Params` (which this change doesn't touch) so `Transport`
+/// stays self-contained; `Default` reproduces the simulation's original
+/// instantaneous, perfectly reliable delivery, so existing callers of
+/// `Network::new` see no behavior change.
+#[derive(Clone, Copy)]
+pub struct TransportConfig {
+    pub message_loss_rate: f64,
+    pub message_latency: u64,
+    pub message_latency_jitter: u64,
+    pub retransmit_timeout: u64,
+    pub retransmit_max_retries: u32,
+}
+
+impl Default for TransportConfig {
+    fn default() -> Self {
+        TransportConfig {
+            message_loss_rate: 0.0,
+            message_latency: 0,
+            message_latency_jitter: 0,
+            retransmit_timeout: 20,
+            retransmit_max_retries: 5,
+        }
+    }
+}
+
+/// A message scheduled for delivery at a particular future tick.
+struct InFlight {
+    /// `None` for a guaranteed-delivery message (`seq.is_some()`): its only
+    /// live copy stays in `outstanding` until `drain_due` takes it from
+    /// there by value, so a retransmit never needs to clone a `Message`.
+    /// Always `Some` for a best-effort message (`seq.is_none()`), which has
+    /// no `outstanding` entry to hold it instead.
+    message: Option<Message>,
+    /// Tick of the original `send`, kept distinct from the per-attempt
+    /// scheduling below so a retransmitted message's delivered latency is
+    /// measured end-to-end rather than just its last leg.
+    first_sent_at: u64,
+    deliver_at: u64,
+    seq: Option<SeqId>,
+}
+
+/// A guaranteed-delivery message that has been sent but not yet observed as
+/// delivered.
+struct Outstanding {
+    message: Message,
+    /// Tick of the original `send`, carried forward across retransmits for
+    /// end-to-end latency accounting once the message is finally delivered.
+    first_sent_at: u64,
+    /// Tick of the most recent send/retransmit attempt, used to decide
+    /// whether the current attempt has timed out.
+    sent_at: u64,
+    retries: u32,
+}
+
+/// Simulated unreliable delivery substrate sitting between the producer of
+/// `Action::Send` and `Section::receive`.
+///
+/// Messages are not handed to their target directly: each one is enqueued
+/// with a delivery tick of the configured latency plus random jitter (the
+/// same tick it was sent under the default, zero-latency configuration),
+/// and may be dropped outright
+/// according to `TransportConfig::message_loss_rate`. Messages that require
+/// guaranteed delivery (currently only `Message::RelocateCommit`) are kept
+/// in an outstanding table keyed by sequence id regardless of whether the
+/// send itself was rolled as lost; delivery acts as the implicit ack, and
+/// if one isn't observed within `retransmit_timeout` ticks the message is
+/// re-enqueued, up to `retransmit_max_retries` attempts, after which it is
+/// abandoned and counted as dropped.
+pub struct Transport {
+    config: TransportConfig,
+    next_seq: SeqId,
+    queue: Vec<InFlight>,
+    outstanding: HashMap<SeqId, Outstanding>,
+    drops: u64,
+    retransmits: u64,
+    delivered: u64,
+    total_latency: u64,
+}
+
+impl Transport {
+    pub fn new(config: TransportConfig) -> Self {
+        Transport {
+            config,
+            next_seq: 0,
+            queue: Vec::new(),
+            outstanding: HashMap::default(),
+            drops: 0,
+            retransmits: 0,
+            delivered: 0,
+            total_latency: 0,
+        }
+    }
+
+    /// Enqueue `message` for delivery at some tick `>= now`, dropping it
+    /// according to the configured loss rate instead. A dropped
+    /// guaranteed-delivery message is still recorded as outstanding, so
+    /// `retransmit_due` can recover it on timeout exactly as it would an
+    /// ack that never arrived; it's only counted in `drops` if that
+    /// recovery later exhausts its retry budget (see `retransmit_due`). A
+    /// dropped best-effort message has no such recovery, so it's counted
+    /// here, immediately.
+    pub fn send(&mut self, now: u64, message: Message) {
+        if Self::requires_ack(&message) {
+            let seq = self.next_seq;
+            self.next_seq += 1;
+            let lost = Self::rolls_as_lost(self.config.message_loss_rate);
+            let deliver_at = now + self.latency();
+
+            // `message` moves into `outstanding` here, even if this attempt
+            // rolls as lost, so `retransmit_due` has something to resend;
+            // the queue entry below never carries its own copy (see
+            // `InFlight::message`).
+            let _ = self.outstanding.insert(
+                seq,
+                Outstanding {
+                    message,
+                    first_sent_at: now,
+                    sent_at: now,
+                    retries: 0,
+                },
+            );
+
+            if !lost {
+                self.queue.push(InFlight {
+                    message: None,
+                    first_sent_at: now,
+                    deliver_at,
+                    seq: Some(seq),
+                });
+            }
+            return;
+        }
+
+        if Self::rolls_as_lost(self.config.message_loss_rate) {
+            self.drops += 1;
+            return;
+        }
+
+        let deliver_at = now + self.latency();
+
+        self.queue.push(InFlight {
+            message: Some(message),
+            first_sent_at: now,
+            deliver_at,
+            seq: None,
+        });
+    }
+
+    fn requires_ack(message: &Message) -> bool {
+        match *message {
+            Message::RelocateCommit { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Whether a single send attempt is rolled as lost at the given loss
+    /// rate. Pulled out of `send` as its own function so the boundary
+    /// behavior (never at `0.0`, always at `1.0`) can be tested without
+    /// needing a `Message` to call `send` itself.
+    fn rolls_as_lost(loss_rate: f64) -> bool {
+        rand::thread_rng().gen::<f64>() < loss_rate
+    }
+
+    fn latency(&self) -> u64 {
+        let jitter = rand::thread_rng().gen_range(0, self.config.message_latency_jitter + 1);
+        self.config.message_latency + jitter
+    }
+
+    /// Whether a message scheduled for `deliver_at` is due by `now`. Pulled
+    /// out of `drain_due`'s partition closure so the ordering rule can be
+    /// tested directly.
+    fn is_due(deliver_at: u64, now: u64) -> bool {
+        deliver_at <= now
+    }
+
+    /// Remove and return every message whose scheduled delivery tick is
+    /// `<= now`. Guaranteed-delivery messages drained here are treated as
+    /// acked: they're removed from the outstanding table. A message whose
+    /// `seq` is no longer in the outstanding table (because `retransmit_due`
+    /// abandoned it after its retry budget ran out, leaving its queued copy
+    /// behind) is discarded rather than delivered, since it has already
+    /// been counted as dropped.
+    pub fn drain_due(&mut self, now: u64) -> Vec<Message> {
+        let (due, pending) = self.queue.drain(..).partition(|in_flight: &InFlight| {
+            Self::is_due(in_flight.deliver_at, now)
+        });
+        self.queue = pending;
+
+        let mut delivered = Vec::with_capacity(due.len());
+        for in_flight in due {
+            if let Some(seq) = in_flight.seq {
+                if let Some(outstanding) = self.outstanding.remove(&seq) {
+                    self.delivered += 1;
+                    self.total_latency += now - in_flight.first_sent_at;
+                    delivered.push(outstanding.message);
+                }
+            } else {
+                self.delivered += 1;
+                self.total_latency += now - in_flight.first_sent_at;
+                delivered.push(in_flight.message.expect(
+                    "best-effort InFlight always carries its own message",
+                ));
+            }
+        }
+        delivered
+    }
+
+    /// Re-enqueue outstanding guaranteed-delivery messages that haven't been
+    /// acked within the configured timeout. Any copy of the message still
+    /// sitting in the delivery queue from the original send (or an earlier
+    /// retransmit) is purged first, so exactly one copy of a given `seq` is
+    /// ever in flight and `drain_due` can't deliver it twice. Messages that
+    /// exhaust their retry budget are abandoned and counted as drops.
+    pub fn retransmit_due(&mut self, now: u64) {
+        let timed_out: Vec<SeqId> = self.outstanding
+            .iter()
+            .filter(|&(_, outstanding)| {
+                now.saturating_sub(outstanding.sent_at) >= self.config.retransmit_timeout
+            })
+            .map(|(&seq, _)| seq)
+            .collect();
+
+        for seq in timed_out {
+            let mut outstanding = self.outstanding.remove(&seq).unwrap();
+
+            if outstanding.retries >= self.config.retransmit_max_retries {
+                self.drops += 1;
+                continue;
+            }
+
+            self.queue.retain(|in_flight| in_flight.seq != Some(seq));
+
+            let first_sent_at = outstanding.first_sent_at;
+            outstanding.retries += 1;
+            outstanding.sent_at = now;
+            let deliver_at = now + self.latency();
+            let _ = self.outstanding.insert(seq, outstanding);
+            self.retransmits += 1;
+
+            self.queue.push(InFlight {
+                message: None,
+                first_sent_at,
+                deliver_at,
+                seq: Some(seq),
+            });
+        }
+    }
+
+    pub fn drops(&self) -> u64 {
+        self.drops
+    }
+
+    pub fn retransmits(&self) -> u64 {
+        self.retransmits
+    }
+
+    /// Mean end-to-end delivery latency in ticks, across every message
+    /// delivered so far.
+    pub fn average_latency(&self) -> f64 {
+        if self.delivered == 0 {
+            0.0
+        } else {
+            self.total_latency as f64 / self.delivered as f64
+        }
+    }
+
+    /// Messages delivered so far, across the whole run. Exposed (alongside
+    /// `total_latency`) so a caller that snapshots both before and after a
+    /// tick can derive that tick's own average latency; `average_latency`
+    /// only ever gives the whole-run mean.
+    pub fn delivered(&self) -> u64 {
+        self.delivered
+    }
+
+    /// Sum of end-to-end delivery latency (in ticks) across every message
+    /// delivered so far. See `delivered`.
+    pub fn total_latency(&self) -> u64 {
+        self.total_latency
+    }
+}
+
+// `send`/`drain_due`/`retransmit_due` themselves still have no direct unit
+// tests: all three take or return a `Message`, and message.rs genuinely
+// isn't present in this source tree (not merely untouched by this series —
+// `use message::Message` resolves to nothing we can read), so there's no
+// field list to construct a `RelocateCommit`, or any other variant, from.
+// What's covered below instead is every piece of the scheduling logic that
+// doesn't depend on `Message`'s shape: the loss-roll decision, the due-tick
+// ordering rule, and the latency accounting, all factored out above so they
+// can be exercised directly. The message-carrying plumbing around them is
+// exercised indirectly through `Network` instead.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loss_rate_zero_never_rolls_as_lost() {
+        for _ in 0..100 {
+            assert!(!Transport::rolls_as_lost(0.0));
+        }
+    }
+
+    #[test]
+    fn loss_rate_one_always_rolls_as_lost() {
+        for _ in 0..100 {
+            assert!(Transport::rolls_as_lost(1.0));
+        }
+    }
+
+    #[test]
+    fn is_due_at_or_after_deliver_at_only() {
+        assert!(!Transport::is_due(10, 9));
+        assert!(Transport::is_due(10, 10));
+        assert!(Transport::is_due(10, 11));
+    }
+
+    #[test]
+    fn zero_jitter_latency_is_exactly_the_configured_value() {
+        let transport = Transport::new(TransportConfig {
+            message_latency: 7,
+            message_latency_jitter: 0,
+            ..TransportConfig::default()
+        });
+
+        for _ in 0..100 {
+            assert_eq!(transport.latency(), 7);
+        }
+    }
+
+    #[test]
+    fn average_latency_is_zero_with_nothing_delivered() {
+        let transport = Transport::new(TransportConfig::default());
+
+        assert_eq!(transport.average_latency(), 0.0);
+    }
+
+    #[test]
+    fn average_latency_divides_total_by_delivered_count() {
+        let mut transport = Transport::new(TransportConfig::default());
+        transport.delivered = 4;
+        transport.total_latency = 30;
+
+        assert_eq!(transport.average_latency(), 7.5);
+    }
+}